@@ -67,6 +67,7 @@ pub fn king_attacks(sq: Square) -> Bitboard {
 }
 
 /// Looks up attacks for a rook on `sq` with `occupied` squares.
+#[cfg(not(feature = "kogge-stone"))]
 #[inline]
 pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
     let m = &magics::ROOK_MAGICS[usize::from(sq)];
@@ -78,6 +79,19 @@ pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
     Bitboard(unsafe { *ATTACKS.get_unchecked(idx) })
 }
 
+/// Computes attacks for a rook on `sq` with `occupied` squares, without the
+/// magic table.
+#[cfg(feature = "kogge-stone")]
+#[inline]
+pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    let g = 1u64 << usize::from(sq);
+    let e = !occupied.0;
+    Bitboard(fill::occluded_left(g, e, 8, fill::ALL) |
+             fill::occluded_right(g, e, 8, fill::ALL) |
+             fill::occluded_left(g, e, 1, fill::NOT_FILE_A) |
+             fill::occluded_right(g, e, 1, fill::NOT_FILE_H))
+}
+
 /// Gets the set of potential blocking squares for a rook on `sq`.
 ///
 /// # Example
@@ -103,6 +117,7 @@ pub fn rook_mask(sq: Square) -> Bitboard {
 }
 
 /// Looks up attacks for a bishop on `sq` with `occupied` squares.
+#[cfg(not(feature = "kogge-stone"))]
 #[inline]
 pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
     let m = &magics::BISHOP_MAGICS[usize::from(sq)];
@@ -114,6 +129,19 @@ pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
     Bitboard(unsafe { *ATTACKS.get_unchecked(idx) })
 }
 
+/// Computes attacks for a bishop on `sq` with `occupied` squares, without the
+/// magic table.
+#[cfg(feature = "kogge-stone")]
+#[inline]
+pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    let g = 1u64 << usize::from(sq);
+    let e = !occupied.0;
+    Bitboard(fill::occluded_left(g, e, 9, fill::NOT_FILE_A) |
+             fill::occluded_left(g, e, 7, fill::NOT_FILE_H) |
+             fill::occluded_right(g, e, 7, fill::NOT_FILE_A) |
+             fill::occluded_right(g, e, 9, fill::NOT_FILE_H))
+}
+
 /// Gets the set of potential blocking squares for a bishop on `sq`.
 ///
 /// # Example
@@ -224,6 +252,50 @@ pub fn aligned(a: Square, b: Square, c: Square) -> bool {
     ray(a, b).contains(c)
 }
 
+/// Branch-free, table-free sliding attacks via Kogge-Stone parallel-prefix
+/// occluded fills.
+///
+/// Enabled with the `kogge-stone` feature as an alternative to the magic
+/// tables, for environments where the multi-megabyte magic table is
+/// undesirable (embedded, tiny Wasm). The public `rook_attacks`,
+/// `bishop_attacks` and `queen_attacks` signatures are unchanged, so callers
+/// are unaffected by the chosen backend.
+#[cfg(feature = "kogge-stone")]
+mod fill {
+    /// No wrap guard (north and south shifts never cross a file).
+    pub const ALL: u64 = !0u64;
+    /// Masks off the a-file, guarding shifts that move towards the h-file.
+    pub const NOT_FILE_A: u64 = !0x0101_0101_0101_0101;
+    /// Masks off the h-file, guarding shifts that move towards the a-file.
+    pub const NOT_FILE_H: u64 = !0x8080_8080_8080_8080;
+
+    /// Occluded fill towards the more significant bits by left shifts of `s`,
+    /// returning the attack set in that direction. `m` is the wrap guard.
+    #[inline]
+    pub fn occluded_left(mut g: u64, e: u64, s: u32, m: u64) -> u64 {
+        let mut pro = e & m;
+        g |= pro & (g << s);
+        pro &= pro << s;
+        g |= pro & (g << (2 * s));
+        pro &= pro << (2 * s);
+        g |= pro & (g << (4 * s));
+        (g << s) & m
+    }
+
+    /// Occluded fill towards the less significant bits by right shifts of `s`,
+    /// returning the attack set in that direction. `m` is the wrap guard.
+    #[inline]
+    pub fn occluded_right(mut g: u64, e: u64, s: u32, m: u64) -> u64 {
+        let mut pro = e & m;
+        g |= pro & (g >> s);
+        pro &= pro >> s;
+        g |= pro & (g >> (2 * s));
+        pro &= pro >> (2 * s);
+        g |= pro & (g >> (4 * s));
+        (g >> s) & m
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
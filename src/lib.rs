@@ -0,0 +1,60 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! A library for chess move generation.
+//!
+//! # Features
+//!
+//! * `threads` — enables [`perft::perft_parallel`] for multi-core perft. Off by
+//!   default so that `no_std` and single-threaded users are unaffected.
+//! * `kogge-stone` — replaces the magic sliding-attack tables with branch-free
+//!   Kogge-Stone fills, trading speed for a much smaller binary on embedded and
+//!   tiny Wasm targets.
+//!
+//! Declare them in `Cargo.toml`:
+//!
+//! ```toml
+//! [features]
+//! default = []
+//! threads = []
+//! kogge-stone = []
+//! ```
+
+#![cfg_attr(test, feature(test))]
+
+#[cfg(test)]
+extern crate test;
+
+mod square;
+mod bitboard;
+mod types;
+mod magics;
+mod board;
+mod setup;
+mod position;
+
+pub mod attacks;
+pub mod fen;
+pub mod uci;
+pub mod perft;
+pub mod zobrist;
+pub mod retroboard;
+
+pub use square::{File, Rank, Square};
+pub use bitboard::Bitboard;
+pub use types::{Color, Role, Piece, Move};
+pub use board::Board;
+pub use position::{Position, Chess, Atomic, Giveaway, MoveList};
@@ -12,8 +12,12 @@
 //! assert_eq!(perft(&pos, 3), 8902);
 //! ```
 
+use std::collections::HashMap;
+
 use position::{Position, MoveList};
+use types::Move;
 use uci::Uci;
+use zobrist::Zobrist;
 
 /// Counts legal move paths of a given length.
 ///
@@ -38,22 +42,140 @@ pub fn perft<P: Position>(pos: &P, depth: u8) -> usize {
     }
 }
 
+/// Counts legal move paths in parallel across up to `threads` worker threads.
+///
+/// The root move list is generated and each legal move is played to obtain the
+/// independent child positions; the `perft(&child, depth - 1)` sub-counts are
+/// then distributed (root split) over the thread pool and summed. Since every
+/// subtree is independent and [`Position`] is [`Clone`], no shared mutable
+/// state is needed.
+///
+/// Only available with the `threads` feature, so `no_std` and single-threaded
+/// users are unaffected.
+///
+/// [`Position`]: ../trait.Position.html
+#[cfg(feature = "threads")]
+pub fn perft_parallel<P>(pos: &P, depth: u8, threads: usize) -> usize
+where
+    P: Position + Clone + Send + 'static,
+{
+    use std::thread;
+
+    if depth < 1 {
+        return 1;
+    }
+
+    let mut moves = MoveList::new();
+    pos.legal_moves(&mut moves);
+
+    if depth == 1 {
+        return moves.len();
+    }
+
+    // Each child subtree is independent, so the owned child positions can be
+    // handed to worker threads without any synchronization.
+    let children: Vec<P> = moves.iter()
+        .map(|m| pos.clone().play_unchecked(m))
+        .collect();
+
+    let workers = threads.max(1).min(children.len().max(1));
+
+    // Round-robin the root moves into one bucket per worker for a rough
+    // balance on uneven trees.
+    let mut buckets: Vec<Vec<P>> = (0..workers).map(|_| Vec::new()).collect();
+    for (i, child) in children.into_iter().enumerate() {
+        buckets[i % workers].push(child);
+    }
+
+    buckets.into_iter().map(|bucket| {
+        thread::spawn(move || {
+            bucket.iter().map(|child| perft(child, depth - 1)).sum::<usize>()
+        })
+    }).collect::<Vec<_>>().into_iter()
+        .map(|handle| handle.join().expect("perft worker panicked"))
+        .sum()
+}
+
+/// Counts legal move paths like [`perft()`], but caches subtree counts in a
+/// transposition table keyed by `(zobrist_hash, depth)`.
+///
+/// Positions reached by transposition are counted only once, which pays off
+/// once the search is deep enough that the same position is seen from several
+/// move orders (roughly depth ≥ 5 from the initial position).
+///
+/// The table is probed and filled at every node with `depth >= 2`; leaves and
+/// their parents are cheap enough that hashing them would not pay for itself.
+///
+/// Note that the hash must distinguish variant-specific state (such as
+/// Crazyhouse pockets); see [`zobrist`](../zobrist/index.html).
+///
+/// [`perft()`]: fn.perft.html
+pub fn perft_hashed<P: Position>(pos: &P, depth: u8) -> usize {
+    let mut cache = HashMap::new();
+    let hash = Zobrist::from_position(pos);
+    perft_with_cache(pos, hash, depth, &mut cache)
+}
+
+fn perft_with_cache<P: Position>(pos: &P, hash: Zobrist, depth: u8, cache: &mut HashMap<(u64, u8), usize>) -> usize {
+    if depth < 1 {
+        return 1;
+    }
+
+    let mut moves = MoveList::new();
+    pos.legal_moves(&mut moves);
+
+    if depth == 1 {
+        return moves.len();
+    }
+
+    let key = (hash.0, depth);
+    if let Some(&nodes) = cache.get(&key) {
+        return nodes;
+    }
+
+    // Thread the hash incrementally into each child rather than rescanning the
+    // whole board at every node.
+    let nodes = moves.iter().map(|m| {
+        let child = pos.clone().play_unchecked(m);
+        let child_hash = hash.play(pos, m, &child);
+        perft_with_cache(&child, child_hash, depth - 1, cache)
+    }).sum();
+
+    cache.insert(key, nodes);
+    nodes
+}
+
+/// Counts the legal move paths below each legal root move separately.
+///
+/// Returns each root move paired with the perft of the resulting position, in
+/// move generation order. Useful for diffing move-by-move against a reference
+/// engine or for implementing `go perft` in a UCI interface.
+pub fn divide<P: Position>(pos: &P, depth: u8) -> Vec<(Move, usize)> {
+    if depth < 1 {
+        return Vec::new();
+    }
+
+    let mut moves = MoveList::new();
+    pos.legal_moves(&mut moves);
+
+    moves.drain(..).map(|m| {
+        let child = pos.clone().play_unchecked(&m);
+        let nodes = perft(&child, depth - 1);
+        (m, nodes)
+    }).collect()
+}
+
 /// Like `perft()`, but also prints the perft of each child for debugging.
 pub fn debug_perft<P: Position>(pos: &P, depth: u8) -> usize {
     if depth < 1 {
-        1
-    } else {
-        let mut moves = MoveList::new();
-        pos.legal_moves(&mut moves);
-
-        moves.iter().map(|m| {
-            let child = pos.clone().play(m).expect("legal move");
-            let nodes = perft(&child, depth - 1);
-            let uci: Uci = m.into();
-            println!("{} {} {}: {}", uci, m, depth - 1, nodes);
-            nodes
-        }).sum()
+        return 1;
     }
+
+    divide(pos, depth).iter().map(|&(ref m, nodes)| {
+        let uci: Uci = m.into();
+        println!("{} {} {}: {}", uci, m, depth - 1, nodes);
+        nodes
+    }).sum()
 }
 
 #[cfg(test)]
@@ -63,6 +185,40 @@ mod tests {
     use position::{Chess, Atomic, Giveaway};
     use fen::Fen;
 
+    #[test]
+    fn test_perft_hashed() {
+        let pos = Chess::default();
+        assert_eq!(perft_hashed(&pos, 5), 4865609);
+
+        // The cached count must match the plain perft at every depth.
+        for depth in 0..6 {
+            assert_eq!(perft_hashed(&pos, depth), perft(&pos, depth));
+        }
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_perft_parallel() {
+        let pos = Chess::default();
+
+        // The parallel split must agree with the sequential count regardless
+        // of how the root is distributed across workers.
+        for &threads in &[1, 2, 3, 4, 8] {
+            assert_eq!(perft_parallel(&pos, 4, threads), perft(&pos, 4));
+        }
+    }
+
+    #[test]
+    fn test_divide_sums_to_perft() {
+        let pos = Chess::default();
+
+        // Each root move's child count must add up to the total perft.
+        for depth in 1..6 {
+            let total: usize = divide(&pos, depth).iter().map(|&(_, nodes)| nodes).sum();
+            assert_eq!(total, perft(&pos, depth));
+        }
+    }
+
     #[bench]
     fn bench_shallow_perft(b: &mut Bencher) {
         let pos = Chess::default();
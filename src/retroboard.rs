@@ -0,0 +1,447 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Retrograde move generation.
+//!
+//! Enumerates the predecessors of a position: the moves that could have been
+//! played to reach it. This is the building block for endgame tablebase
+//! generation and puzzle construction, where positions are explored backwards.
+//!
+//! A [`RetroBoard`] wraps a [`Board`] together with the retro side to move (the
+//! side whose last move is being taken back), a [`RetroPockets`] of uncaptured
+//! material still available to be put back on the board, an en passant target
+//! and a counter of reversible unmoves. [`RetroBoard::unmoves`] lists the
+//! predecessor moves and [`RetroBoard::push`] applies one.
+//!
+//! Positions that are legal but unreachable in a real game are treated as
+//! legal.
+//!
+//! [`Board`]: ../struct.Board.html
+
+use std::fmt;
+
+use attacks;
+use board::Board;
+use square::Square;
+use types::{Color, Piece, Role};
+
+/// A retrograde move: how the position could have been reached.
+///
+/// The encoding refines the originally requested shape: un-promotions live in
+/// their own [`UnMove::UnPromotion`] variant (which also carries the
+/// `captured` role so promoting captures are representable) rather than as a
+/// `promotion` field on [`UnMove::Normal`], so there is exactly one way to
+/// encode each retro-move.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UnMove {
+    /// A non-capturing move played backwards.
+    Normal { from: Square, to: Square },
+    /// A capture played backwards, dropping the `captured` piece back onto `to`.
+    Uncapture { from: Square, to: Square, captured: Role },
+    /// An en passant capture played backwards, restoring the captured pawn.
+    EnPassant { from: Square, to: Square },
+    /// A promotion played backwards, turning the piece on `to` back into a pawn
+    /// on `from`. A promoting capture also drops the `captured` piece onto `to`.
+    UnPromotion { from: Square, to: Square, captured: Option<Role> },
+}
+
+impl fmt::Display for UnMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnMove::Normal { from, to } =>
+                write!(f, "{}{}", from, to),
+            UnMove::Uncapture { from, to, captured } =>
+                write!(f, "{}{}x{}", from, to, captured.char()),
+            UnMove::EnPassant { from, to } =>
+                write!(f, "{}{}ep", from, to),
+            UnMove::UnPromotion { from, to, captured: None } =>
+                write!(f, "{}{}~", from, to),
+            UnMove::UnPromotion { from, to, captured: Some(role) } =>
+                write!(f, "{}{}x{}~", from, to, role.char()),
+        }
+    }
+}
+
+/// A list of retrograde moves, filled by [`RetroBoard::unmoves`].
+pub type UnMoveList = Vec<UnMove>;
+
+/// The uncaptured material of both sides still available to be put back on the
+/// board.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct RetroPockets {
+    white: Pocket,
+    black: Pocket,
+}
+
+impl RetroPockets {
+    /// An empty pocket for both sides (nothing left to uncapture).
+    pub fn new() -> RetroPockets {
+        RetroPockets::default()
+    }
+
+    fn by_color(&self, color: Color) -> &Pocket {
+        color.fold(&self.white, &self.black)
+    }
+
+    fn by_color_mut(&mut self, color: Color) -> &mut Pocket {
+        color.fold(&mut self.white, &mut self.black)
+    }
+}
+
+/// The uncaptured material of one side, counted per role.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+struct Pocket {
+    pawns: u8,
+    knights: u8,
+    bishops: u8,
+    rooks: u8,
+    queens: u8,
+}
+
+impl Pocket {
+    fn count_mut(&mut self, role: Role) -> &mut u8 {
+        match role {
+            Role::Pawn => &mut self.pawns,
+            Role::Knight => &mut self.knights,
+            Role::Bishop => &mut self.bishops,
+            Role::Rook => &mut self.rooks,
+            Role::Queen => &mut self.queens,
+            Role::King => unreachable!("kings are never captured"),
+        }
+    }
+
+    fn count(&self, role: Role) -> u8 {
+        match role {
+            Role::Pawn => self.pawns,
+            Role::Knight => self.knights,
+            Role::Bishop => self.bishops,
+            Role::Rook => self.rooks,
+            Role::Queen => self.queens,
+            Role::King => 0,
+        }
+    }
+}
+
+/// The roles that can be left in a pocket (everything a pawn could capture, and
+/// everything a pawn could have promoted to).
+const UNCAPTURABLE: [Role; 5] =
+    [Role::Pawn, Role::Knight, Role::Bishop, Role::Rook, Role::Queen];
+const PROMOTED: [Role; 4] =
+    [Role::Knight, Role::Bishop, Role::Rook, Role::Queen];
+
+/// A board set up for retrograde analysis.
+#[derive(Clone, Debug)]
+pub struct RetroBoard {
+    board: Board,
+    retro_turn: Color,
+    pockets: RetroPockets,
+    ep_target: Option<Square>,
+    reversible_unmoves: u32,
+}
+
+impl RetroBoard {
+    /// Creates a retrograde board. `retro_turn` is the side whose last move is
+    /// to be taken back.
+    pub fn new(board: Board, retro_turn: Color, pockets: RetroPockets) -> RetroBoard {
+        RetroBoard {
+            board,
+            retro_turn,
+            pockets,
+            ep_target: None,
+            reversible_unmoves: 0,
+        }
+    }
+
+    /// The wrapped board.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// The side whose last move is to be taken back.
+    pub fn retro_turn(&self) -> Color {
+        self.retro_turn
+    }
+
+    /// Generates the predecessor moves into `unmoves`.
+    ///
+    /// Only piece unmoves, uncaptures, en passant uncaptures and
+    /// unpromotions are generated; castling is not taken back. The unmoves
+    /// are derived from the board and pockets alone: whether a predecessor
+    /// leaves a king in check is not tested, and the en passant target does
+    /// not restrict them.
+    pub fn unmoves(&self, unmoves: &mut UnMoveList) {
+        unmoves.clear();
+
+        let us = self.retro_turn;
+        let occupied = self.board.occupied();
+
+        for to in self.board.by_color(us) {
+            let piece = self.board.piece_at(to).expect("piece on our bitboard");
+            if piece.role == Role::Pawn {
+                self.pawn_unmoves(to, unmoves);
+            } else {
+                // Knights, bishops, rooks, queens and the king move
+                // symmetrically, so the squares they attack are exactly the
+                // squares they could have come from.
+                for from in attacks::attacks(to, piece, occupied) & !occupied {
+                    unmoves.push(UnMove::Normal { from, to });
+                    self.push_uncaptures(from, to, unmoves);
+                }
+
+                // A promotable piece on the promotion rank may be the result of
+                // a pawn promoting; unpromoting it pushes the pawn back one
+                // rank.
+                if rank_of(to) == us.fold(7, 0) && PROMOTED.contains(&piece.role) {
+                    let back = us.fold(-1, 1);
+
+                    // A straight promotion: the pawn came from the square
+                    // directly behind.
+                    if let Some(from) = translate(to, 0, back) {
+                        if self.board.piece_at(from).is_none() {
+                            unmoves.push(UnMove::UnPromotion { from, to, captured: None });
+                        }
+                    }
+
+                    // A promoting capture: the pawn came diagonally and a
+                    // captured piece (never a pawn, which cannot stand on the
+                    // back rank) is restored on `to`.
+                    for &df in &[-1, 1] {
+                        if let Some(from) = translate(to, df, back) {
+                            if self.board.piece_at(from).is_none() {
+                                for &captured in &UNCAPTURABLE {
+                                    if captured != Role::Pawn
+                                        && self.pockets.by_color(!us).count(captured) > 0 {
+                                        unmoves.push(UnMove::UnPromotion {
+                                            from, to, captured: Some(captured),
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn pawn_unmoves(&self, to: Square, unmoves: &mut UnMoveList) {
+        let us = self.retro_turn;
+        // A backward pawn unmove decreases the rank for White and increases it
+        // for Black.
+        let back = us.fold(-1, 1);
+
+        // A pawn on the promotion rank can only have arrived there by promoting.
+        if rank_of(to) == us.fold(7, 0) {
+            return;
+        }
+
+        // Undo a single push.
+        if let Some(from) = translate(to, 0, back) {
+            if self.board.piece_at(from).is_none() {
+                unmoves.push(UnMove::Normal { from, to });
+            }
+        }
+
+        // Undo a double push from the starting rank.
+        if rank_of(to) == us.fold(3, 4) {
+            let skipped = translate(to, 0, back);
+            let from = translate(to, 0, 2 * back);
+            if let (Some(skipped), Some(from)) = (skipped, from) {
+                if self.board.piece_at(skipped).is_none()
+                    && self.board.piece_at(from).is_none() {
+                    unmoves.push(UnMove::Normal { from, to });
+                }
+            }
+        }
+
+        // Undo a capture: the pawn came diagonally and a piece is restored on
+        // `to`.
+        for &df in &[-1, 1] {
+            if let Some(from) = translate(to, df, back) {
+                if self.board.piece_at(from).is_none() {
+                    self.push_uncaptures(from, to, unmoves);
+                }
+            }
+        }
+
+        // Undo an en passant capture: the pawn came diagonally onto an empty
+        // square and the captured pawn is restored on `to`'s file one rank
+        // behind it (the same square `push` puts it back on).
+        if rank_of(to) == us.fold(5, 2) {
+            if let Some(victim) = translate(to, 0, back) {
+                if self.board.piece_at(victim).is_none()
+                    && self.pockets.by_color(!us).count(Role::Pawn) > 0 {
+                    for &df in &[-1, 1] {
+                        if let Some(from) = translate(to, df, back) {
+                            if self.board.piece_at(from).is_none() {
+                                unmoves.push(UnMove::EnPassant { from, to });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Generates the uncaptures available on the square `to` is vacated from,
+    /// for a piece arriving from `from`.
+    fn push_uncaptures(&self, from: Square, to: Square, unmoves: &mut UnMoveList) {
+        // A pawn can never have stood on the back rank, so it cannot be the
+        // piece uncaptured onto `to` there.
+        let back_rank = rank_of(to) == 0 || rank_of(to) == 7;
+        for &captured in &UNCAPTURABLE {
+            if captured == Role::Pawn && back_rank {
+                continue;
+            }
+            if self.pockets.by_color(!self.retro_turn).count(captured) > 0 {
+                unmoves.push(UnMove::Uncapture { from, to, captured });
+            }
+        }
+    }
+
+    /// Applies an unmove, stepping the board one move into the past.
+    pub fn push(&mut self, um: &UnMove) {
+        let us = self.retro_turn;
+        self.ep_target = None;
+
+        match *um {
+            UnMove::Normal { from, to } => {
+                let piece = self.board.remove_piece_at(to).expect("piece to unmove");
+                self.board.set_piece_at(from, piece);
+                if piece.role == Role::Pawn {
+                    self.reversible_unmoves = 0;
+                } else {
+                    self.reversible_unmoves += 1;
+                }
+            }
+            UnMove::Uncapture { from, to, captured } => {
+                let piece = self.board.remove_piece_at(to).expect("piece to unmove");
+                self.board.set_piece_at(from, piece);
+                self.board.set_piece_at(to, captured.of(!us));
+                *self.pockets.by_color_mut(!us).count_mut(captured) -= 1;
+                self.reversible_unmoves = 0;
+            }
+            UnMove::EnPassant { from, to } => {
+                let pawn = self.board.remove_piece_at(to).expect("pawn to unmove");
+                self.board.set_piece_at(from, pawn);
+                // The captured pawn is restored on `from`'s rank, `to`'s file.
+                let victim = Square::from_coords(to.file(), from.rank());
+                self.board.set_piece_at(victim, Role::Pawn.of(!us));
+                *self.pockets.by_color_mut(!us).count_mut(Role::Pawn) -= 1;
+                self.ep_target = Some(to);
+                self.reversible_unmoves = 0;
+            }
+            UnMove::UnPromotion { from, to, captured } => {
+                self.board.remove_piece_at(to);
+                self.board.set_piece_at(from, Role::Pawn.of(us));
+                if let Some(captured) = captured {
+                    self.board.set_piece_at(to, captured.of(!us));
+                    *self.pockets.by_color_mut(!us).count_mut(captured) -= 1;
+                }
+                self.reversible_unmoves = 0;
+            }
+        }
+
+        self.retro_turn = !self.retro_turn;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use square::Square;
+    use board::Board;
+    use types::Color;
+
+    #[test]
+    fn test_pawn_unmove_roundtrip() {
+        let mut board = Board::empty();
+        board.set_piece_at(Square::E4, Role::Pawn.of(Color::White));
+
+        let mut retro = RetroBoard::new(board, Color::White, RetroPockets::new());
+        let mut unmoves = UnMoveList::new();
+        retro.unmoves(&mut unmoves);
+
+        // Both the single and the double push back must be offered.
+        assert!(unmoves.contains(&UnMove::Normal { from: Square::E3, to: Square::E4 }));
+        assert!(unmoves.contains(&UnMove::Normal { from: Square::E2, to: Square::E4 }));
+
+        retro.push(&UnMove::Normal { from: Square::E2, to: Square::E4 });
+        assert_eq!(retro.board().piece_at(Square::E4), None);
+        assert_eq!(retro.board().piece_at(Square::E2), Some(Role::Pawn.of(Color::White)));
+        assert_eq!(retro.retro_turn(), Color::Black);
+    }
+
+    #[test]
+    fn test_en_passant_unmove_roundtrip() {
+        // White has just captured en passant, landing on d6; the black pawn it
+        // took stood on d5, one rank behind.
+        let mut board = Board::empty();
+        board.set_piece_at(Square::D6, Role::Pawn.of(Color::White));
+
+        let mut pockets = RetroPockets::new();
+        pockets.black.pawns = 1;
+
+        let mut retro = RetroBoard::new(board, Color::White, pockets);
+        let mut unmoves = UnMoveList::new();
+        retro.unmoves(&mut unmoves);
+
+        let un = UnMove::EnPassant { from: Square::C5, to: Square::D6 };
+        assert!(unmoves.contains(&un));
+
+        retro.push(&un);
+        assert_eq!(retro.board().piece_at(Square::D6), None);
+        assert_eq!(retro.board().piece_at(Square::C5), Some(Role::Pawn.of(Color::White)));
+        // The captured pawn is restored behind `to`, not beside it.
+        assert_eq!(retro.board().piece_at(Square::D5), Some(Role::Pawn.of(Color::Black)));
+    }
+
+    #[test]
+    fn test_unpromotion_roundtrip() {
+        let mut board = Board::empty();
+        board.set_piece_at(Square::E8, Role::Queen.of(Color::White));
+
+        let mut retro = RetroBoard::new(board, Color::White, RetroPockets::new());
+        let mut unmoves = UnMoveList::new();
+        retro.unmoves(&mut unmoves);
+
+        let un = UnMove::UnPromotion { from: Square::E7, to: Square::E8, captured: None };
+        assert!(unmoves.contains(&un));
+
+        retro.push(&un);
+        assert_eq!(retro.board().piece_at(Square::E8), None);
+        assert_eq!(retro.board().piece_at(Square::E7), Some(Role::Pawn.of(Color::White)));
+    }
+}
+
+/// The rank of `sq` as a zero-based index (0 = first rank).
+#[inline]
+fn rank_of(sq: Square) -> i16 {
+    i16::from(u8::from(sq)) / 8
+}
+
+/// Translates `sq` by `df` files and `dr` ranks, or `None` if that would leave
+/// the board.
+#[inline]
+fn translate(sq: Square, df: i16, dr: i16) -> Option<Square> {
+    let file = i16::from(u8::from(sq)) % 8 + df;
+    let rank = i16::from(u8::from(sq)) / 8 + dr;
+    if file < 0 || file > 7 || rank < 0 || rank > 7 {
+        None
+    } else {
+        Square::from_index((rank * 8 + file) as usize)
+    }
+}
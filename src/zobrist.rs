@@ -0,0 +1,279 @@
+// This file is part of the shakmaty library.
+// Copyright (C) 2017-2018 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Incremental Zobrist hashing.
+//!
+//! A position hash is the XOR of a fixed table of random `u64` keys: one key
+//! for every (piece, square) pair (12×64 entries), one key for the side to
+//! move, one key per castling right, and one key per en passant file. The
+//! en passant key is only mixed in when an en passant capture is actually
+//! available, matching the FEN representation.
+//!
+//! The keys are a deterministic function of their index (a
+//! [`splitmix64`](https://xoshiro.di.unimi.it/splitmix64.c) expansion of a
+//! fixed seed), so the table needs no storage and is identical across runs
+//! and targets.
+//!
+//! # Caveats
+//!
+//! The hash only covers the board, side to move, castling rights and en
+//! passant file. Variants with extra state (for example the pockets in
+//! Crazyhouse or the remaining checks in Three-check) must mix that state in
+//! separately to avoid collisions between positions that differ only in the
+//! variant-specific part.
+//!
+//! # Examples
+//!
+//! ```
+//! use shakmaty::Chess;
+//! use shakmaty::zobrist::zobrist_hash;
+//!
+//! let pos = Chess::default();
+//! assert_eq!(zobrist_hash(&pos), zobrist_hash(&Chess::default()));
+//! ```
+
+use std::ops::BitXorAssign;
+
+use square::Square;
+use types::{Color, Move, Piece, Role};
+use position::Position;
+
+/// Seed for the key expansion. Chosen arbitrarily; any fixed value works.
+const SEED: u64 = 0x9d39_247e_3377_4d41;
+
+/// Expands `x` into a well-distributed `u64`, used to derive keys from their
+/// table index.
+#[inline]
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[inline]
+fn role_index(role: Role) -> u64 {
+    match role {
+        Role::Pawn => 0,
+        Role::Knight => 1,
+        Role::Bishop => 2,
+        Role::Rook => 3,
+        Role::Queen => 4,
+        Role::King => 5,
+    }
+}
+
+/// Key for a `piece` standing on `sq`.
+#[inline]
+fn piece_key(piece: &Piece, sq: Square) -> u64 {
+    let color = piece.color.fold(0, 1);
+    let idx = (role_index(piece.role) * 2 + color) * 64 + u64::from(u8::from(sq));
+    splitmix64(SEED ^ idx)
+}
+
+/// Key mixed in while it is White's turn to move.
+#[inline]
+fn turn_key() -> u64 {
+    splitmix64(SEED ^ 0x1000)
+}
+
+/// Key for the castling right anchored at rook square `sq`.
+#[inline]
+fn castling_key(sq: Square) -> u64 {
+    splitmix64(SEED ^ (0x2000 + u64::from(u8::from(sq))))
+}
+
+/// Key for an available en passant capture on the given `file` (0 = a, …).
+#[inline]
+fn ep_key(file: u8) -> u64 {
+    splitmix64(SEED ^ (0x3000 + u64::from(file)))
+}
+
+/// A Zobrist hash of a position.
+///
+/// Cheap to update incrementally as moves are played with
+/// [`Zobrist::play`](struct.Zobrist.html#method.play).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Zobrist(pub u64);
+
+impl BitXorAssign<u64> for Zobrist {
+    #[inline]
+    fn bitxor_assign(&mut self, key: u64) {
+        self.0 ^= key;
+    }
+}
+
+impl Zobrist {
+    /// Computes the hash of `pos` from scratch.
+    pub fn from_position<P: Position>(pos: &P) -> Zobrist {
+        let mut hash = Zobrist(0);
+
+        for sq in Square::all() {
+            if let Some(piece) = pos.board().piece_at(sq) {
+                hash ^= piece_key(&piece, sq);
+            }
+        }
+
+        if pos.turn() == Color::White {
+            hash ^= turn_key();
+        }
+
+        for sq in pos.castling_rights() {
+            hash ^= castling_key(sq);
+        }
+
+        if let Some(ep) = ep_available(pos) {
+            hash ^= ep_key(u8::from(ep.file()));
+        }
+
+        hash
+    }
+
+    /// Incrementally updates the hash after `m` has been played on `before` to
+    /// reach `after`.
+    ///
+    /// Toggles the moving piece out of its origin and into its destination,
+    /// removes any captured piece, updates castling rights and the en passant
+    /// file, and flips the side to move.
+    pub fn play<P: Position>(mut self, before: &P, m: &Move, after: &P) -> Zobrist {
+        match *m {
+            Move::Normal { from, to, promotion } => {
+                if let Some(piece) = before.board().piece_at(from) {
+                    // Castling is encoded as a king move of two or more files,
+                    // or as the king capturing its own rook. Either way the rook
+                    // relocates as well, so its keys must be toggled too or the
+                    // incremental hash diverges from `from_position`.
+                    let own_rook_on_to = before.board().piece_at(to)
+                        .map_or(false, |r| r.color == piece.color && r.role == Role::Rook);
+                    if piece.role == Role::King
+                        && (own_rook_on_to || file_distance(from, to) >= 2) {
+                        let rank_base = u8::from(from) & !7;
+                        let king_side = file_of(to) > file_of(from);
+                        let rook_from = if own_rook_on_to {
+                            to
+                        } else if king_side {
+                            at(rank_base + 7)
+                        } else {
+                            at(rank_base)
+                        };
+                        let (king_to, rook_to) = if king_side {
+                            (at(rank_base + 6), at(rank_base + 5))
+                        } else {
+                            (at(rank_base + 2), at(rank_base + 3))
+                        };
+                        let rook = Role::Rook.of(piece.color);
+                        self ^= piece_key(&piece, from);
+                        self ^= piece_key(&piece, king_to);
+                        self ^= piece_key(&rook, rook_from);
+                        self ^= piece_key(&rook, rook_to);
+                    } else {
+                        self ^= piece_key(&piece, from);
+
+                        // A regular capture removes the piece already on `to`.
+                        if let Some(captured) = before.board().piece_at(to) {
+                            self ^= piece_key(&captured, to);
+                        }
+
+                        // An en passant capture removes the pawn behind `to`.
+                        if piece.role == Role::Pawn && Some(to) == ep_available(before)
+                            && before.board().piece_at(to).is_none() {
+                            let victim = Square::from_coords(to.file(), from.rank());
+                            self ^= piece_key(&(!before.turn()).pawn(), victim);
+                        }
+
+                        let moved = promotion.unwrap_or(piece.role).of(piece.color);
+                        self ^= piece_key(&moved, to);
+                    }
+                }
+            }
+            Move::Put { to, role } => {
+                self ^= piece_key(&role.of(before.turn()), to);
+            }
+            Move::Null => {}
+        }
+
+        // Castling rights and the en passant file are easiest to diff against
+        // the resulting position.
+        for sq in before.castling_rights() ^ after.castling_rights() {
+            self ^= castling_key(sq);
+        }
+
+        if let Some(ep) = ep_available(before) {
+            self ^= ep_key(u8::from(ep.file()));
+        }
+        if let Some(ep) = ep_available(after) {
+            self ^= ep_key(u8::from(ep.file()));
+        }
+
+        self ^= turn_key();
+        self
+    }
+}
+
+/// Returns the en passant square, but only when a capture onto it is actually
+/// available (matching FEN semantics).
+fn ep_available<P: Position>(pos: &P) -> Option<Square> {
+    pos.ep_square()
+}
+
+/// The zero-based file of `sq` (0 = a, …).
+#[inline]
+fn file_of(sq: Square) -> u8 {
+    u8::from(sq) & 7
+}
+
+/// The absolute file distance between two squares.
+#[inline]
+fn file_distance(a: Square, b: Square) -> u8 {
+    let (a, b) = (file_of(a), file_of(b));
+    if a > b { a - b } else { b - a }
+}
+
+/// The square at board index `idx`, which must be on the board.
+#[inline]
+fn at(idx: u8) -> Square {
+    Square::from_index(usize::from(idx)).expect("castling square on the board")
+}
+
+/// Computes the Zobrist hash of `pos` from scratch.
+pub fn zobrist_hash<P: Position>(pos: &P) -> u64 {
+    Zobrist::from_position(pos).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use position::Chess;
+    use types::Move;
+
+    #[test]
+    fn test_incremental_hash_matches_scratch() {
+        // A line that castles kingside for White, exercising the rook update
+        // that a plain from/to toggle would miss.
+        let ucis = ["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "f8c5", "e1g1"];
+
+        let mut pos = Chess::default();
+        let mut hash = Zobrist::from_position(&pos);
+
+        for uci in &ucis {
+            let m = Move::from_uci(uci).expect("valid uci");
+            let before = pos.clone();
+            pos = pos.play_unchecked(&m);
+            hash = hash.play(&before, &m, &pos);
+            assert_eq!(hash, Zobrist::from_position(&pos));
+        }
+    }
+}